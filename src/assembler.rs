@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use crate::cpu::{Instruction, Register};
+
+/// A single parsed source line, not yet resolved against the symbol table.
+struct Entry<'a> {
+    line: usize,
+    addr: u32,
+    mnemonic: &'a str,
+    fields: Vec<&'a str>,
+}
+
+#[derive(Debug)]
+pub enum AssembleError {
+    UnknownMnemonic { line: usize, mnemonic: String },
+    MissingField { line: usize, mnemonic: String },
+    InvalidField { line: usize, field: String },
+    UndefinedLabel { line: usize, label: String },
+    DuplicateLabel { line: usize, label: String },
+}
+
+impl std::fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssembleError::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "line {line}: unknown mnemonic `{mnemonic}`")
+            }
+            AssembleError::MissingField { line, mnemonic } => {
+                write!(f, "line {line}: `{mnemonic}` is missing a field")
+            }
+            AssembleError::InvalidField { line, field } => {
+                write!(f, "line {line}: invalid field `{field}`")
+            }
+            AssembleError::UndefinedLabel { line, label } => {
+                write!(f, "line {line}: undefined label `{label}`")
+            }
+            AssembleError::DuplicateLabel { line, label } => {
+                write!(f, "line {line}: duplicate label `{label}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+const MNEMONICS: &[&str] = &[
+    "add", "nor", "lw", "sw", "beq", "jalr", "halt", "noop", ".fill",
+];
+
+fn is_mnemonic(token: &str) -> bool {
+    MNEMONICS.contains(&token)
+}
+
+/// Assembles LC-2K source text into the `u32` memory image that `CPU::new` consumes.
+///
+/// Lines are of the form `[label] opcode field0 field1 field2`; blank lines and lines
+/// starting with `#` are ignored. Runs as two passes: the first assigns every
+/// instruction a sequential address and records `label -> address`, the second encodes
+/// each instruction, resolving symbolic operands along the way.
+pub fn assemble(source: &str) -> Result<Vec<u32>, AssembleError> {
+    let mut symbols = HashMap::new();
+    let mut entries = Vec::new();
+    let mut addr = 0u32;
+
+    for (line, raw) in source.lines().enumerate() {
+        let mut tokens = raw.split_whitespace().peekable();
+        let Some(first) = tokens.peek().copied() else {
+            continue;
+        };
+        if first.starts_with('#') {
+            continue;
+        }
+
+        let mnemonic = if is_mnemonic(first) {
+            tokens.next();
+            first
+        } else {
+            let label = tokens.next().unwrap();
+            let mnemonic = tokens.next().ok_or(AssembleError::MissingField {
+                line,
+                mnemonic: "label".to_string(),
+            })?;
+            if !is_mnemonic(mnemonic) {
+                return Err(AssembleError::UnknownMnemonic {
+                    line,
+                    mnemonic: mnemonic.to_string(),
+                });
+            }
+            if symbols.insert(label.to_string(), addr).is_some() {
+                return Err(AssembleError::DuplicateLabel {
+                    line,
+                    label: label.to_string(),
+                });
+            }
+            mnemonic
+        };
+
+        entries.push(Entry {
+            line,
+            addr,
+            mnemonic,
+            fields: tokens.collect(),
+        });
+        addr += 1;
+    }
+
+    entries
+        .iter()
+        .map(|entry| encode_entry(entry, &symbols))
+        .collect()
+}
+
+fn encode_entry(entry: &Entry, symbols: &HashMap<String, u32>) -> Result<u32, AssembleError> {
+    let field = |index: usize| {
+        entry
+            .fields
+            .get(index)
+            .copied()
+            .ok_or(AssembleError::MissingField {
+                line: entry.line,
+                mnemonic: entry.mnemonic.to_string(),
+            })
+    };
+    let register = |token: &str| parse_register(entry.line, token);
+
+    match entry.mnemonic {
+        "add" => Ok(Instruction::Add {
+            reg_a: register(field(0)?)?,
+            reg_b: register(field(1)?)?,
+            dst_reg: register(field(2)?)?,
+        }
+        .encode()),
+        "nor" => Ok(Instruction::Nor {
+            reg_a: register(field(0)?)?,
+            reg_b: register(field(1)?)?,
+            dst_reg: register(field(2)?)?,
+        }
+        .encode()),
+        "lw" => Ok(Instruction::Lw {
+            reg_a: register(field(0)?)?,
+            reg_b: register(field(1)?)?,
+            offset_field: resolve_absolute(entry.line, field(2)?, symbols)?,
+        }
+        .encode()),
+        "sw" => Ok(Instruction::Sw {
+            reg_a: register(field(0)?)?,
+            reg_b: register(field(1)?)?,
+            offset_field: resolve_absolute(entry.line, field(2)?, symbols)?,
+        }
+        .encode()),
+        "beq" => Ok(Instruction::Beq {
+            reg_a: register(field(0)?)?,
+            reg_b: register(field(1)?)?,
+            offset_field: resolve_relative(entry.line, field(2)?, entry.addr, symbols)?,
+        }
+        .encode()),
+        "jalr" => Ok(Instruction::Jalr {
+            reg_a: register(field(0)?)?,
+            reg_b: register(field(1)?)?,
+        }
+        .encode()),
+        "halt" => Ok(Instruction::Halt.encode()),
+        "noop" => Ok(Instruction::Noop.encode()),
+        ".fill" => resolve_fill(entry.line, field(0)?, symbols),
+        mnemonic => Err(AssembleError::UnknownMnemonic {
+            line: entry.line,
+            mnemonic: mnemonic.to_string(),
+        }),
+    }
+}
+
+fn parse_register(line: usize, token: &str) -> Result<Register, AssembleError> {
+    let invalid = || AssembleError::InvalidField {
+        line,
+        field: token.to_string(),
+    };
+    let index = token.parse::<u32>().map_err(|_| invalid())?;
+    if index > 7 {
+        return Err(invalid());
+    }
+    Ok(Register::new(index))
+}
+
+/// Narrows a resolved offset to `i16`, reporting out-of-range values as an
+/// invalid field instead of silently truncating.
+fn narrow_offset(line: usize, token: &str, value: i64) -> Result<i16, AssembleError> {
+    i16::try_from(value).map_err(|_| AssembleError::InvalidField {
+        line,
+        field: token.to_string(),
+    })
+}
+
+/// An `lw`/`sw` offset resolves a symbol to its absolute address.
+fn resolve_absolute(
+    line: usize,
+    token: &str,
+    symbols: &HashMap<String, u32>,
+) -> Result<i16, AssembleError> {
+    if let Ok(value) = token.parse::<i64>() {
+        return narrow_offset(line, token, value);
+    }
+    let addr = symbols
+        .get(token)
+        .ok_or_else(|| AssembleError::UndefinedLabel {
+            line,
+            label: token.to_string(),
+        })?;
+    narrow_offset(line, token, *addr as i64)
+}
+
+/// A `beq` offset resolves a symbol to the signed distance from the next instruction.
+fn resolve_relative(
+    line: usize,
+    token: &str,
+    this_addr: u32,
+    symbols: &HashMap<String, u32>,
+) -> Result<i16, AssembleError> {
+    if let Ok(value) = token.parse::<i64>() {
+        return narrow_offset(line, token, value);
+    }
+    let target = symbols
+        .get(token)
+        .ok_or_else(|| AssembleError::UndefinedLabel {
+            line,
+            label: token.to_string(),
+        })?;
+    narrow_offset(line, token, *target as i64 - (this_addr as i64 + 1))
+}
+
+fn resolve_fill(
+    line: usize,
+    token: &str,
+    symbols: &HashMap<String, u32>,
+) -> Result<u32, AssembleError> {
+    if let Ok(value) = token.parse::<i64>() {
+        return Ok(value as u32);
+    }
+    symbols
+        .get(token)
+        .copied()
+        .ok_or_else(|| AssembleError::UndefinedLabel {
+            line,
+            label: token.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_relative_targets_pc_plus_one() {
+        let mut symbols = HashMap::new();
+        symbols.insert("target".to_string(), 4);
+        let offset = resolve_relative(0, "target", 0, &symbols).unwrap();
+        assert_eq!(offset, 3);
+    }
+
+    #[test]
+    fn assemble_resolves_labeled_beq_to_pc_plus_one_offset() {
+        let image = assemble("beq 0 0 target\nnoop\nnoop\nnoop\ntarget halt\n").unwrap();
+        let beq = Instruction::new(image[0]);
+        match beq {
+            Instruction::Beq { offset_field, .. } => assert_eq!(offset_field, 3),
+            other => panic!("expected a Beq instruction, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn fill_resolves_a_label_to_its_address() {
+        let image = assemble("noop\ntarget halt\ndata .fill target\n").unwrap();
+        assert_eq!(image[2], 1);
+    }
+
+    #[test]
+    fn assemble_resolves_labeled_lw_to_absolute_address() {
+        let image = assemble("lw 0 1 target\ntarget halt\n").unwrap();
+        let lw = Instruction::new(image[0]);
+        match lw {
+            Instruction::Lw { offset_field, .. } => assert_eq!(offset_field, 1),
+            other => panic!("expected an Lw instruction, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn lw_field_out_of_range_for_i16_is_invalid_field() {
+        let err = assemble("lw 0 1 40000\n").unwrap_err();
+        assert!(matches!(err, AssembleError::InvalidField { field, .. } if field == "40000"));
+    }
+
+    #[test]
+    fn duplicate_label_is_an_error() {
+        let err = assemble("a halt\na halt\n").unwrap_err();
+        assert!(matches!(err, AssembleError::DuplicateLabel { label, .. } if label == "a"));
+    }
+
+    #[test]
+    fn undefined_label_is_an_error() {
+        let err = assemble("beq 0 0 nowhere\n").unwrap_err();
+        assert!(matches!(err, AssembleError::UndefinedLabel { label, .. } if label == "nowhere"));
+    }
+
+    #[test]
+    fn missing_field_is_an_error() {
+        let err = assemble("add 0 1\n").unwrap_err();
+        assert!(matches!(err, AssembleError::MissingField { mnemonic, .. } if mnemonic == "add"));
+    }
+}
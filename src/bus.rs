@@ -0,0 +1,235 @@
+use std::io::Write;
+
+/// A memory-mapped peripheral: one 32-bit register addressed by an offset
+/// relative to wherever the `Bus` has mapped it. `None`/`false` signal that
+/// `offset` is out of range for this device.
+pub(crate) trait Device {
+    fn read(&self, offset: u32) -> Option<u32>;
+    fn write(&mut self, offset: u32, value: u32) -> bool;
+
+    /// Side-effect-free read, used to snapshot the old value before a write
+    /// (e.g. for the undo journal). Defaults to `read`; devices whose reads
+    /// have side effects (blocking on stdin, popping a queue, ...) should
+    /// override this instead of reusing `read`.
+    fn peek(&self, offset: u32) -> Option<u32> {
+        self.read(offset)
+    }
+
+    /// Whether a write to this device is a plain, invertible cell (like RAM)
+    /// rather than a one-shot side effect (like printing a character).
+    /// Defaults to `false`; a device's undo journal entry should only be
+    /// replayed on `back` when this is `true`.
+    fn is_reversible(&self) -> bool {
+        false
+    }
+}
+
+/// Flat RAM, used as the default device for any address no peripheral has claimed.
+pub(crate) struct Ram {
+    cells: Box<[u32]>,
+}
+
+impl Ram {
+    fn new(size: u32) -> Self {
+        Ram {
+            cells: vec![0; size as usize].into_boxed_slice(),
+        }
+    }
+}
+
+impl Device for Ram {
+    fn read(&self, offset: u32) -> Option<u32> {
+        self.cells.get(offset as usize).copied()
+    }
+
+    fn write(&mut self, offset: u32, value: u32) -> bool {
+        match self.cells.get_mut(offset as usize) {
+            Some(cell) => {
+                *cell = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn is_reversible(&self) -> bool {
+        true
+    }
+}
+
+const CONSOLE_OUTPUT: u32 = 0;
+const CONSOLE_INPUT: u32 = 1;
+
+/// A two-word console device: a write to the output port prints a character,
+/// a read from the input port pulls a word from stdin.
+pub(crate) struct Console {
+    /// The last word written to the output port, tracked so a watchpoint on
+    /// it can see writes via `peek` without re-printing on every `run` loop.
+    last_output: u32,
+}
+
+impl Console {
+    pub(crate) fn new() -> Self {
+        Console { last_output: 0 }
+    }
+}
+
+impl Device for Console {
+    fn read(&self, offset: u32) -> Option<u32> {
+        if offset != CONSOLE_INPUT {
+            return Some(0);
+        }
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line).is_err() {
+            return Some(0);
+        }
+        Some(line.trim().parse().unwrap_or(0))
+    }
+
+    fn write(&mut self, offset: u32, value: u32) -> bool {
+        if offset != CONSOLE_OUTPUT {
+            return true;
+        }
+        self.last_output = value;
+        print!("{}", value as u8 as char);
+        let _ = std::io::stdout().flush();
+        true
+    }
+
+    fn peek(&self, offset: u32) -> Option<u32> {
+        if offset == CONSOLE_INPUT {
+            Some(0)
+        } else {
+            Some(self.last_output)
+        }
+    }
+}
+
+/// Maps non-overlapping address ranges to devices, falling back to flat RAM
+/// for any address no device has claimed.
+pub(crate) struct Bus {
+    ram: Ram,
+    regions: Vec<(u32, u32, Box<dyn Device>)>,
+}
+
+impl Bus {
+    pub(crate) fn new(ram_size: u32) -> Self {
+        Bus {
+            ram: Ram::new(ram_size),
+            regions: Vec::new(),
+        }
+    }
+
+    /// Claims `[start, start + len)` for `device`, consulted ahead of RAM.
+    pub(crate) fn map(&mut self, start: u32, len: u32, device: Box<dyn Device>) {
+        self.regions.push((start, len, device));
+    }
+
+    /// Loads `words` into RAM starting at address 0, as `CPU::new` does for a
+    /// pre-assembled memory image. Words beyond the end of RAM are dropped.
+    pub(crate) fn load<T: Iterator<Item = u32>>(&mut self, words: T) {
+        for (addr, word) in words.enumerate() {
+            self.ram.write(addr as u32, word);
+        }
+    }
+
+    fn region_for(&self, addr: u32) -> Option<usize> {
+        self.regions
+            .iter()
+            .position(|(start, len, _)| addr >= *start && addr < start + len)
+    }
+
+    /// Returns `None` if `addr` is not claimed by any device and falls outside RAM.
+    pub(crate) fn read(&self, addr: u32) -> Option<u32> {
+        match self.region_for(addr) {
+            Some(i) => {
+                let (start, _, device) = &self.regions[i];
+                device.read(addr - start)
+            }
+            None => self.ram.read(addr),
+        }
+    }
+
+    /// Side-effect-free counterpart to `read`, for snapshotting a value before a write.
+    pub(crate) fn peek(&self, addr: u32) -> Option<u32> {
+        match self.region_for(addr) {
+            Some(i) => {
+                let (start, _, device) = &self.regions[i];
+                device.peek(addr - start)
+            }
+            None => self.ram.peek(addr),
+        }
+    }
+
+    /// Whether a write to `addr` can be undone by writing back the old value,
+    /// i.e. the target is plain RAM rather than a side-effecting device.
+    pub(crate) fn is_reversible(&self, addr: u32) -> bool {
+        match self.region_for(addr) {
+            Some(i) => self.regions[i].2.is_reversible(),
+            None => true,
+        }
+    }
+
+    /// Returns `false` if `addr` is not claimed by any device and falls outside RAM.
+    pub(crate) fn write(&mut self, addr: u32, value: u32) -> bool {
+        match self.region_for(addr) {
+            Some(i) => {
+                let (start, _, device) = &mut self.regions[i];
+                device.write(addr - *start, value)
+            }
+            None => self.ram.write(addr, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ram_read_write_round_trips() {
+        let mut ram = Ram::new(4);
+        assert_eq!(ram.read(0), Some(0));
+        assert!(ram.write(2, 42));
+        assert_eq!(ram.read(2), Some(42));
+        assert_eq!(ram.read(4), None);
+        assert!(!ram.write(4, 1));
+    }
+
+    struct Tagged(u32);
+
+    impl Device for Tagged {
+        fn read(&self, offset: u32) -> Option<u32> {
+            Some(self.0 + offset)
+        }
+
+        fn write(&mut self, _offset: u32, _value: u32) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn mapped_region_takes_precedence_over_ram() {
+        let mut bus = Bus::new(8);
+        bus.write(2, 99);
+        bus.map(2, 2, Box::new(Tagged(1000)));
+
+        assert_eq!(bus.read(2), Some(1000));
+        assert_eq!(bus.read(3), Some(1001));
+        assert_eq!(bus.read(1), Some(0));
+        assert_eq!(bus.read(4), Some(0));
+    }
+
+    #[test]
+    fn console_input_peek_does_not_block_on_stdin() {
+        assert_eq!(Console::new().peek(CONSOLE_INPUT), Some(0));
+    }
+
+    #[test]
+    fn console_output_peek_reflects_last_write() {
+        let mut console = Console::new();
+        assert_eq!(console.peek(CONSOLE_OUTPUT), Some(0));
+        console.write(CONSOLE_OUTPUT, 65);
+        assert_eq!(console.peek(CONSOLE_OUTPUT), Some(65));
+    }
+}
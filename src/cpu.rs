@@ -1,5 +1,9 @@
+use std::collections::HashSet;
+
+use crate::bus::{Bus, Console};
+
 #[derive(Clone, Copy, Debug)]
-enum Register {
+pub(crate) enum Register {
     R0 = 0,
     R1 = 1,
     R2 = 2,
@@ -11,7 +15,7 @@ enum Register {
 }
 
 impl Register {
-    fn new(index: u32) -> Self {
+    pub(crate) fn new(index: u32) -> Self {
         match index & 0b111 {
             0b000 => Self::R0,
             0b001 => Self::R1,
@@ -33,7 +37,7 @@ impl std::fmt::Display for Register {
 }
 
 #[derive(Debug)]
-enum Instruction {
+pub(crate) enum Instruction {
     Add {
         reg_a: Register,
         reg_b: Register,
@@ -68,7 +72,7 @@ enum Instruction {
 }
 
 impl Instruction {
-    fn new(code: u32) -> Self {
+    pub(crate) fn new(code: u32) -> Self {
         match code >> 22 & 0b111 {
             0b000 => {
                 let (reg_a, reg_b, dst_reg) = Instruction::parse_r(code);
@@ -139,6 +143,52 @@ impl Instruction {
     fn parse_j(code: u32) -> (Register, Register) {
         (Register::new(code >> 19), Register::new(code >> 16))
     }
+
+    /// Inverse of `new`: packs this instruction back into its machine word.
+    pub(crate) fn encode(&self) -> u32 {
+        match self {
+            Instruction::Add {
+                reg_a,
+                reg_b,
+                dst_reg,
+            } => Instruction::encode_r(0b000, *reg_a, *reg_b, *dst_reg),
+            Instruction::Nor {
+                reg_a,
+                reg_b,
+                dst_reg,
+            } => Instruction::encode_r(0b001, *reg_a, *reg_b, *dst_reg),
+            Instruction::Lw {
+                reg_a,
+                reg_b,
+                offset_field,
+            } => Instruction::encode_i(0b010, *reg_a, *reg_b, *offset_field),
+            Instruction::Sw {
+                reg_a,
+                reg_b,
+                offset_field,
+            } => Instruction::encode_i(0b011, *reg_a, *reg_b, *offset_field),
+            Instruction::Beq {
+                reg_a,
+                reg_b,
+                offset_field,
+            } => Instruction::encode_i(0b100, *reg_a, *reg_b, *offset_field),
+            Instruction::Jalr { reg_a, reg_b } => Instruction::encode_j(0b101, *reg_a, *reg_b),
+            Instruction::Halt => 0b110 << 22,
+            Instruction::Noop => 0b111 << 22,
+        }
+    }
+
+    fn encode_r(opcode: u32, reg_a: Register, reg_b: Register, dst_reg: Register) -> u32 {
+        (opcode << 22) | ((reg_a as u32) << 19) | ((reg_b as u32) << 16) | (dst_reg as u32)
+    }
+
+    fn encode_i(opcode: u32, reg_a: Register, reg_b: Register, offset_field: i16) -> u32 {
+        (opcode << 22) | ((reg_a as u32) << 19) | ((reg_b as u32) << 16) | (offset_field as u16 as u32)
+    }
+
+    fn encode_j(opcode: u32, reg_a: Register, reg_b: Register) -> u32 {
+        (opcode << 22) | ((reg_a as u32) << 19) | ((reg_b as u32) << 16)
+    }
 }
 
 impl std::fmt::Display for Instruction {
@@ -200,26 +250,157 @@ impl std::fmt::Display for Instruction {
     }
 }
 
-const MEMORY_SIZE: usize = 65536;
+const MEMORY_SIZE: u32 = 65536;
+const CONSOLE_BASE: u32 = MEMORY_SIZE - 2;
+const CONSOLE_LEN: u32 = 2;
+
+/// Why `CPU::run` stopped looping.
+pub enum StopReason {
+    Halted,
+    Breakpoint(u32),
+    Watchpoint(u32),
+}
+
+/// A runtime fault raised by `CPU::step` instead of panicking.
 #[derive(Debug)]
+pub enum Trap {
+    /// An `lw`/`sw` addressed a cell the bus has no device for.
+    MemoryFault { addr: u32 },
+    /// A `beq`/`jalr` jumped to an address the bus has no device for.
+    PcOutOfBounds { pc: u32 },
+    /// Execution ran off the end of memory without hitting a `halt`.
+    ExecutedPastEnd,
+}
+
+impl std::fmt::Display for Trap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Trap::MemoryFault { addr } => write!(f, "memory fault: address 0x{addr:X} is out of bounds"),
+            Trap::PcOutOfBounds { pc } => write!(f, "program counter out of bounds: 0x{pc:X}"),
+            Trap::ExecutedPastEnd => write!(f, "execution ran past the end of memory without halting"),
+        }
+    }
+}
+
+impl std::error::Error for Trap {}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::Halted => write!(f, "Program has halted"),
+            StopReason::Breakpoint(addr) => write!(f, "Stopped at breakpoint 0x{addr:X}"),
+            StopReason::Watchpoint(addr) => write!(f, "Stopped: watchpoint triggered at 0x{addr:X}"),
+        }
+    }
+}
+
+/// A single register or memory write, whichever (at most one) an instruction made.
+enum Mutation {
+    Register { reg: Register, old_value: u32 },
+    Memory { addr: u32, old_value: u32 },
+}
+
+/// Enough state to undo one `CPU::step`.
+struct JournalEntry {
+    old_pc: u32,
+    mutation: Option<Mutation>,
+    pre_halted: bool,
+}
+
 pub struct CPU {
     register_file: [u32; 8],
-    memory: Box<[u32; MEMORY_SIZE]>,
+    bus: Bus,
     pc: u32,
     halted: bool,
+    instruction_count: usize,
+    breakpoints: HashSet<u32>,
+    watchpoints: HashSet<u32>,
+    journal: Vec<JournalEntry>,
 }
 
 impl CPU {
     pub fn new<T: Iterator<Item = u32>>(starting_memory: T) -> Self {
-        let mut memory = Box::new([0; MEMORY_SIZE]);
-        for (index, item) in starting_memory.enumerate() {
-            memory[index] = item;
-        }
+        let mut bus = Bus::new(MEMORY_SIZE);
+        bus.map(CONSOLE_BASE, CONSOLE_LEN, Box::new(Console::new()));
+        bus.load(starting_memory);
         CPU {
             register_file: [0; 8],
-            memory,
+            bus,
             pc: 0,
             halted: false,
+            instruction_count: 0,
+            breakpoints: HashSet::new(),
+            watchpoints: HashSet::new(),
+            journal: Vec::new(),
+        }
+    }
+
+    pub fn get_instruction_count(&self) -> usize {
+        self.instruction_count
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u32) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u32) {
+        self.breakpoints.remove(&addr);
+    }
+
+    pub fn add_watchpoint(&mut self, addr: u32) {
+        self.watchpoints.insert(addr);
+    }
+
+    /// Rewinds up to `n` executed steps, clamped to how many are in the
+    /// journal. Returns how many were actually reversed.
+    pub fn back(&mut self, n: usize) -> usize {
+        (0..n).take_while(|_| self.undo()).count()
+    }
+
+    fn undo(&mut self) -> bool {
+        let Some(entry) = self.journal.pop() else {
+            return false;
+        };
+        self.pc = entry.old_pc;
+        self.halted = entry.pre_halted;
+        self.instruction_count -= 1;
+        match entry.mutation {
+            Some(Mutation::Register { reg, old_value }) => self.set_register(reg, old_value),
+            Some(Mutation::Memory { addr, old_value }) => {
+                self.bus.write(addr, old_value);
+            }
+            None => {}
+        }
+        true
+    }
+
+    /// Steps until the program halts, hits a breakpoint, or a watched memory
+    /// cell changes.
+    pub fn run(&mut self) -> Result<StopReason, Trap> {
+        loop {
+            if self.halted {
+                return Ok(StopReason::Halted);
+            }
+            let watched_before: Vec<(u32, Option<u32>)> = self
+                .watchpoints
+                .iter()
+                .map(|&addr| (addr, self.bus.peek(addr)))
+                .collect();
+
+            if self.step()? {
+                return Ok(StopReason::Halted);
+            }
+
+            if let Some(&(addr, _)) = watched_before
+                .iter()
+                .find(|&(addr, before)| self.bus.peek(*addr) != *before)
+            {
+                return Ok(StopReason::Watchpoint(addr));
+            }
+
+            if self.breakpoints.contains(&self.pc) {
+                return Ok(StopReason::Breakpoint(self.pc));
+            }
         }
     }
 
@@ -230,8 +411,14 @@ impl CPU {
     }
 
     pub fn print_memory(&self, start_addr: u32, count: u32) {
-        for val in &self.memory[(start_addr as usize)..(start_addr as usize) + (count as usize)] {
-            print!("{:08X} ", val);
+        for i in 0..count {
+            let Some(addr) = start_addr.checked_add(i) else {
+                break;
+            };
+            match self.bus.peek(addr) {
+                Some(val) => print!("{val:08X} "),
+                None => print!("???????? "),
+            }
         }
         println!();
     }
@@ -242,8 +429,15 @@ impl CPU {
 
     pub fn print_instruction(&self, amount: usize) {
         for i in 0..amount {
-            let addr = self.pc as usize + i;
-            println!("0x{addr:X}: {}", Instruction::new(self.memory[addr]));
+            let Some(addr) = self.pc.checked_add(i as u32) else {
+                println!("<out of bounds>");
+                break;
+            };
+            let Some(code) = self.bus.peek(addr) else {
+                println!("0x{addr:X}: <out of bounds>");
+                continue;
+            };
+            println!("0x{addr:X}: {}", Instruction::new(code));
         }
     }
 
@@ -258,66 +452,100 @@ impl CPU {
     }
 
     /// Returns true if the program has halted
-    pub fn step_n(&mut self, count: usize) -> bool {
+    pub fn step_n(&mut self, count: usize) -> Result<bool, Trap> {
         if self.halted {
-            return true;
+            return Ok(true);
         };
         for _ in 0..count {
-            if self.step() {
+            if self.step()? {
                 self.halted = true;
-                return true;
+                return Ok(true);
             }
         }
-        false
+        Ok(false)
     }
 
     /// Returns true if the program has halted
-    pub fn step(&mut self) -> bool {
+    pub fn step(&mut self) -> Result<bool, Trap> {
         if self.halted {
-            return true;
+            return Ok(true);
         };
-        let instruction = self.memory[self.pc as usize];
+        let old_pc = self.pc;
+        let pre_halted = self.halted;
+
+        if self.pc >= CONSOLE_BASE {
+            return Err(Trap::PcOutOfBounds { pc: self.pc });
+        }
+        let instruction = self
+            .bus
+            .read(self.pc)
+            .ok_or(Trap::PcOutOfBounds { pc: self.pc })?;
         let instruction = Instruction::new(instruction);
+
+        let mut mutation = None;
+        let mut jumped = false;
+        let mut halted_now = false;
+
         match instruction {
             Instruction::Add {
                 reg_a,
                 reg_b,
                 dst_reg,
             } => {
+                let old_value = self.get_register(dst_reg);
                 self.set_register(
                     dst_reg,
                     self.get_register(reg_a)
                         .wrapping_add(self.get_register(reg_b)),
                 );
+                mutation = Some(Mutation::Register {
+                    reg: dst_reg,
+                    old_value,
+                });
             }
             Instruction::Nor {
                 reg_a,
                 reg_b,
                 dst_reg,
             } => {
+                let old_value = self.get_register(dst_reg);
                 self.set_register(
                     dst_reg,
                     !(self.get_register(reg_a) | self.get_register(reg_b)),
                 );
+                mutation = Some(Mutation::Register {
+                    reg: dst_reg,
+                    old_value,
+                });
             }
             Instruction::Lw {
                 reg_a,
                 reg_b,
                 offset_field,
             } => {
-                self.set_register(
-                    reg_b,
-                    self.memory
-                        [CPU::offset_memory(self.get_register(reg_a), offset_field) as usize],
-                );
+                let addr = CPU::offset_memory(self.get_register(reg_a), offset_field);
+                let value = self.bus.read(addr).ok_or(Trap::MemoryFault { addr })?;
+                let old_value = self.get_register(reg_b);
+                self.set_register(reg_b, value);
+                mutation = Some(Mutation::Register {
+                    reg: reg_b,
+                    old_value,
+                });
             }
             Instruction::Sw {
                 reg_a,
                 reg_b,
                 offset_field,
             } => {
-                self.memory[CPU::offset_memory(self.get_register(reg_a), offset_field) as usize] =
-                    self.get_register(reg_b);
+                let addr = CPU::offset_memory(self.get_register(reg_a), offset_field);
+                let old_value = self.bus.peek(addr).unwrap_or(0);
+                let reversible = self.bus.is_reversible(addr);
+                if !self.bus.write(addr, self.get_register(reg_b)) {
+                    return Err(Trap::MemoryFault { addr });
+                }
+                if reversible {
+                    mutation = Some(Mutation::Memory { addr, old_value });
+                }
             }
             Instruction::Beq {
                 reg_a,
@@ -325,26 +553,148 @@ impl CPU {
                 offset_field,
             } => {
                 if self.get_register(reg_a) == self.get_register(reg_b) {
-                    self.pc = CPU::offset_memory(self.pc, offset_field);
+                    let target = CPU::offset_memory(self.pc.wrapping_add(1), offset_field);
+                    if target >= CONSOLE_BASE {
+                        return Err(Trap::PcOutOfBounds { pc: target });
+                    }
+                    self.pc = target;
+                    jumped = true;
                 }
             }
             Instruction::Jalr { reg_a, reg_b } => {
+                let target = self.get_register(reg_a);
+                if target >= CONSOLE_BASE {
+                    return Err(Trap::PcOutOfBounds { pc: target });
+                }
+                let old_value = self.get_register(reg_b);
                 self.set_register(reg_b, self.pc + 1);
-                self.pc = self.get_register(reg_a);
-                return false;
+                mutation = Some(Mutation::Register {
+                    reg: reg_b,
+                    old_value,
+                });
+                self.pc = target;
+                jumped = true;
             }
             Instruction::Halt => {
-                self.pc += 1;
                 self.halted = true;
-                return true;
+                halted_now = true;
             }
             Instruction::Noop => {}
         }
-        self.pc += 1;
-        false
+
+        if !jumped {
+            self.pc += 1;
+        }
+
+        self.instruction_count += 1;
+        self.journal.push(JournalEntry {
+            old_pc,
+            mutation,
+            pre_halted,
+        });
+
+        if !halted_now && self.pc >= CONSOLE_BASE {
+            return Err(Trap::ExecutedPastEnd);
+        }
+
+        Ok(halted_now)
     }
 
     fn offset_memory(address: u32, offset_field: i16) -> u32 {
         address.wrapping_add_signed(offset_field as i32)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instruction_encode_new_round_trips() {
+        let instructions = [
+            Instruction::Add {
+                reg_a: Register::R1,
+                reg_b: Register::R2,
+                dst_reg: Register::R3,
+            },
+            Instruction::Nor {
+                reg_a: Register::R4,
+                reg_b: Register::R5,
+                dst_reg: Register::R6,
+            },
+            Instruction::Lw {
+                reg_a: Register::R7,
+                reg_b: Register::R0,
+                offset_field: -5,
+            },
+            Instruction::Sw {
+                reg_a: Register::R2,
+                reg_b: Register::R3,
+                offset_field: 1234,
+            },
+            Instruction::Beq {
+                reg_a: Register::R0,
+                reg_b: Register::R1,
+                offset_field: -1,
+            },
+            Instruction::Jalr {
+                reg_a: Register::R5,
+                reg_b: Register::R6,
+            },
+            Instruction::Halt,
+            Instruction::Noop,
+        ];
+        for instruction in instructions {
+            let code = instruction.encode();
+            assert_eq!(format!("{instruction:?}"), format!("{:?}", Instruction::new(code)));
+        }
+    }
+
+    #[test]
+    fn run_stops_at_breakpoint() {
+        let image = [
+            Instruction::Noop.encode(),
+            Instruction::Noop.encode(),
+            Instruction::Halt.encode(),
+        ];
+        let mut cpu = CPU::new(image.into_iter());
+        cpu.add_breakpoint(1);
+        let stop = cpu.run().unwrap();
+        assert!(matches!(stop, StopReason::Breakpoint(1)));
+    }
+
+    #[test]
+    fn run_stops_at_watchpoint() {
+        let image = [
+            Instruction::Sw {
+                reg_a: Register::R0,
+                reg_b: Register::R1,
+                offset_field: 5,
+            }
+            .encode(),
+            Instruction::Halt.encode(),
+        ];
+        let mut cpu = CPU::new(image.into_iter());
+        cpu.set_register(Register::R1, 7);
+        cpu.add_watchpoint(5);
+        let stop = cpu.run().unwrap();
+        assert!(matches!(stop, StopReason::Watchpoint(5)));
+    }
+
+    #[test]
+    fn taken_beq_targets_pc_plus_one_plus_offset() {
+        let image = [
+            Instruction::Beq {
+                reg_a: Register::R0,
+                reg_b: Register::R0,
+                offset_field: 1,
+            }
+            .encode(),
+            Instruction::Noop.encode(),
+            Instruction::Halt.encode(),
+        ];
+        let mut cpu = CPU::new(image.into_iter());
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 2);
+    }
+}
@@ -1,10 +1,12 @@
 use std::{
     fs::File,
-    io::{BufRead, BufReader, Write},
+    io::{BufRead, BufReader, Read, Write},
     iter,
     str::FromStr,
 };
 
+mod assembler;
+mod bus;
 mod cpu;
 
 #[derive(Debug)]
@@ -13,8 +15,23 @@ enum Error {
     NotEnoughArguments,
     Stdout,
     Stdin,
+    Assemble(assembler::AssembleError),
 }
 
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::FileNotFound => write!(f, "could not open the given memory image file"),
+            Error::NotEnoughArguments => write!(f, "usage: lc2kdb <memory-image-or-.as-file>"),
+            Error::Stdout => write!(f, "failed to write to stdout"),
+            Error::Stdin => write!(f, "failed to read from stdin"),
+            Error::Assemble(err) => write!(f, "assembly error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 const HELP_MESSAGE: &str = r#"h|help           -> show this help message
 s|step `n`       -> step program forward `n` steps (default: 1)
 r|regs           -> show current register values
@@ -22,28 +39,49 @@ m|mem `addr` `n` -> read `n` (default: 1) bits starting from address `addr` (def
 p|pc             -> display current program counter
 i|ins `n`        -> print the `n` (default: 1) memory addresses after pc as instructions
 q|quit           -> close debugger
-c|count          -> print number of clock cycles executed"#;
+c|count          -> print number of clock cycles executed
+b|break `addr`   -> set a breakpoint at `addr`
+d|delete `addr`  -> remove the breakpoint at `addr`
+w|watch `addr`   -> trap when `addr` is written by a store
+g|run            -> run until halt, a breakpoint, or a watchpoint trips
+back `n`         -> rewind `n` (default: 1) executed steps"#;
 
-fn main() -> Result<(), Error> {
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
     let args = std::env::args();
     let args = args.collect::<Vec<_>>();
-    let mut reader = if let Some(ifname) = args.get(1) {
-        BufReader::new(File::open(ifname).map_err(|_| Error::FileNotFound)?)
-    } else {
+    let Some(ifname) = args.get(1) else {
         return Err(Error::NotEnoughArguments);
     };
 
-    let mut line = String::new();
-    let read = || {
-        line.clear();
-        if reader.read_line(&mut line).is_err() {
-            None
-        } else {
-            line.trim_end().parse::<i64>().ok().map(|n| n as u32)
-        }
+    let image = if ifname.ends_with(".as") {
+        let mut source = String::new();
+        File::open(ifname)
+            .map_err(|_| Error::FileNotFound)?
+            .read_to_string(&mut source)
+            .map_err(|_| Error::FileNotFound)?;
+        assembler::assemble(&source).map_err(Error::Assemble)?
+    } else {
+        let mut reader = BufReader::new(File::open(ifname).map_err(|_| Error::FileNotFound)?);
+        let mut line = String::new();
+        let read = || {
+            line.clear();
+            if reader.read_line(&mut line).is_err() {
+                None
+            } else {
+                line.trim_end().parse::<i64>().ok().map(|n| n as u32)
+            }
+        };
+        iter::from_fn(read).collect()
     };
 
-    let mut cpu = cpu::CPU::new(iter::from_fn(read));
+    let mut cpu = cpu::CPU::new(image.into_iter());
 
     let mut line = String::new();
     loop {
@@ -80,8 +118,10 @@ fn process_repl_input<'a, T: Iterator<Item = &'a str>>(
         "help" | "h" => println!("{}", HELP_MESSAGE),
         "step" | "s" => {
             let count = parse_from_arg::<usize>(args.next()).unwrap_or(1);
-            if cpu.step_n(count) {
-                println!("Program has halted");
+            match cpu.step_n(count) {
+                Ok(true) => println!("Program has halted"),
+                Ok(false) => {}
+                Err(trap) => println!("Trap: {trap}"),
             }
         }
         "regs" | "r" => cpu.print_registers(),
@@ -103,6 +143,27 @@ fn process_repl_input<'a, T: Iterator<Item = &'a str>>(
         "count" | "c" => {
             println!("{}", cpu.get_instruction_count());
         }
+        "break" | "b" => {
+            let addr = parse_from_arg::<u32>(args.next())?;
+            cpu.add_breakpoint(addr);
+        }
+        "delete" | "d" => {
+            let addr = parse_from_arg::<u32>(args.next())?;
+            cpu.remove_breakpoint(addr);
+        }
+        "watch" | "w" => {
+            let addr = parse_from_arg::<u32>(args.next())?;
+            cpu.add_watchpoint(addr);
+        }
+        "run" | "g" => match cpu.run() {
+            Ok(reason) => println!("{reason}"),
+            Err(trap) => println!("Trap: {trap}"),
+        },
+        "back" => {
+            let count = parse_from_arg::<usize>(args.next()).unwrap_or(1);
+            let undone = cpu.back(count);
+            println!("Reversed {undone} step(s)");
+        }
         _ => {
             return Err(UnrecognizedCommandError);
         }